@@ -0,0 +1,315 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use crate::s3::{GetFile, Metadata, PutFile, Tags};
+
+/// Filesystem-backed implementation of [`GetFile`]/[`PutFile`], so the thumbnail pipeline
+/// can target a local directory (`file://` destinations, local testing) instead of S3.
+pub(crate) struct LocalFsStore {
+    root: PathBuf,
+}
+
+impl LocalFsStore {
+    pub(crate) fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    /// Joins `bucket`/`key` onto `root`, rejecting any component that could escape it
+    /// (`..`, an absolute path, or a Windows path prefix). `key` in particular is
+    /// attacker-controlled, since it comes straight from the uploaded object's name.
+    fn path_for(&self, bucket: &str, key: &str) -> Result<PathBuf, String> {
+        use std::path::Component;
+
+        for part in [bucket, key] {
+            let has_unsafe_component = std::path::Path::new(part)
+                .components()
+                .any(|component| matches!(component, Component::ParentDir | Component::RootDir | Component::Prefix(_)));
+
+            if has_unsafe_component {
+                return Err(format!("Refusing to use unsafe path component: {}", part));
+            }
+        }
+
+        Ok(self.root.join(bucket).join(key))
+    }
+}
+
+#[async_trait]
+impl GetFile for LocalFsStore {
+    async fn get_file(&self, bucket: &str, key: &str) -> Result<Vec<u8>, String> {
+        fs::read(self.path_for(bucket, key)?).map_err(|err| err.to_string())
+    }
+
+    async fn get_tags(&self, _bucket: &str, _key: &str) -> Result<Tags, String> {
+        // The local filesystem has no tagging concept, so there is nothing to carry over.
+        Ok(vec![])
+    }
+
+    async fn get_metadata(&self, _bucket: &str, _key: &str) -> Result<Metadata, String> {
+        // The local filesystem has no per-object metadata concept, so there is nothing to carry over.
+        Ok(Metadata::new())
+    }
+
+    async fn get_etag(&self, _bucket: &str, _key: &str) -> Result<String, String> {
+        Err("Local filesystem entries have no ETag".to_string())
+    }
+}
+
+#[async_trait]
+impl PutFile for LocalFsStore {
+    async fn put_file(
+        &self,
+        bucket: &str,
+        key: &str,
+        bytes: Vec<u8>,
+        _content_type: &str,
+        _tags: &Tags,
+        _metadata: &Metadata,
+        _source_etag: &str,
+    ) -> Result<String, String> {
+        let path = self.path_for(bucket, key)?;
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+        }
+
+        fs::write(&path, bytes).map_err(|err| err.to_string())?;
+
+        Ok(format!("Wrote a file with key {} into {}", key, bucket))
+    }
+
+    async fn get_existing_source_etag(&self, _bucket: &str, _key: &str) -> Option<String> {
+        // The local filesystem backend doesn't store metadata, so idempotency skipping is disabled.
+        None
+    }
+
+    async fn get_presigned_url(&self, bucket: &str, key: &str, _expires_in: Duration) -> Result<String, String> {
+        // There's nothing to presign locally, so hand back a direct file:// reference instead.
+        Ok(format!("file://{}", self.path_for(bucket, key)?.display()))
+    }
+}
+
+/// The two storage backends a `file://`/`s3://` URI can resolve to, shared by both
+/// [`Source`] (where objects are read from) and [`Destination`] (where thumbnails are written).
+enum StorageUri {
+    S3,
+    LocalDir(PathBuf),
+}
+
+/// Parses a storage URI such as `s3://` (the default) or `file:///var/thumbnails`.
+fn parse_storage_uri(uri: &str) -> Result<StorageUri, String> {
+    if uri.is_empty() || uri == "s3://" {
+        Ok(StorageUri::S3)
+    } else if let Some(path) = uri.strip_prefix("file://") {
+        Ok(StorageUri::LocalDir(PathBuf::from(path)))
+    } else {
+        Err(format!("Unsupported storage URI: {}", uri))
+    }
+}
+
+/// Where source objects should be read from, resolved from `THUMBNAIL_SOURCE_URI`.
+#[derive(Clone, Debug)]
+pub(crate) enum Source {
+    /// Read source objects straight from the S3 bucket named in the event (the default).
+    S3,
+    /// Read source objects from a local directory instead of S3.
+    LocalDir(PathBuf),
+}
+
+impl Source {
+    /// Parses a source URI such as `s3://` (default behavior) or `file:///var/uploads`.
+    pub(crate) fn parse(uri: &str) -> Result<Self, String> {
+        match parse_storage_uri(uri)? {
+            StorageUri::S3 => Ok(Source::S3),
+            StorageUri::LocalDir(path) => Ok(Source::LocalDir(path)),
+        }
+    }
+}
+
+/// Where generated thumbnails should be written, resolved from `THUMBNAIL_DESTINATION_URI`
+/// (and `THUMBNAIL_BUCKET_SUFFIX`). The bucket for a given source bucket comes from [`Destination::bucket_for`].
+#[derive(Clone, Debug)]
+pub(crate) enum Destination {
+    /// Upload alongside the source, in an S3 bucket with the given suffix appended
+    /// to the source bucket's name (`-thumbs` by default).
+    S3BucketSuffix(String),
+    /// Write thumbnails under a local directory instead of S3.
+    LocalDir(PathBuf),
+}
+
+impl Destination {
+    /// Parses a destination URI such as `s3://` (default behavior) or `file:///var/thumbnails`.
+    /// `bucket_suffix` is only used by the `s3://` form, and defaults to `-thumbs` when empty.
+    pub(crate) fn parse(uri: &str, bucket_suffix: &str) -> Result<Self, String> {
+        let bucket_suffix = if bucket_suffix.is_empty() { "-thumbs" } else { bucket_suffix };
+
+        match parse_storage_uri(uri)? {
+            StorageUri::S3 => Ok(Destination::S3BucketSuffix(bucket_suffix.to_owned())),
+            StorageUri::LocalDir(path) => Ok(Destination::LocalDir(path)),
+        }
+    }
+
+    /// Bucket (or bucket-equivalent root) thumbnails for `source_bucket` should be written under.
+    pub(crate) fn bucket_for(&self, source_bucket: &str) -> String {
+        match self {
+            Destination::S3BucketSuffix(suffix) => format!("{}{}", source_bucket, suffix),
+            Destination::LocalDir(_) => source_bucket.to_owned(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    static TEST_DIR_COUNTER: AtomicU32 = AtomicU32::new(0);
+
+    /// A fresh, unique scratch directory for a single test, cleaned up once the test is done.
+    struct ScratchDir(PathBuf);
+
+    impl ScratchDir {
+        fn new() -> Self {
+            let id = TEST_DIR_COUNTER.fetch_add(1, Ordering::Relaxed);
+            let path = std::env::temp_dir().join(format!("thumbnailr-backend-test-{}-{}", std::process::id(), id));
+            fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for ScratchDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_local_fs_store_round_trip() {
+        let scratch = ScratchDir::new();
+        let store = LocalFsStore::new(scratch.0.clone());
+
+        let put_result = store
+            .put_file("my-bucket-thumbs", "photo-256.png", vec![1, 2, 3], "image/png", &vec![], &Metadata::new(), "\"etag\"")
+            .await;
+        assert!(put_result.is_ok());
+
+        let read_back = store.get_file("my-bucket-thumbs", "photo-256.png").await;
+        assert_eq!(read_back.unwrap(), vec![1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_local_fs_store_rejects_path_traversal_in_key() {
+        let scratch = ScratchDir::new();
+        let store = LocalFsStore::new(scratch.0.clone());
+
+        let put_result = store
+            .put_file(
+                "my-bucket-thumbs",
+                "../../../tmp/evil.png",
+                vec![1, 2, 3],
+                "image/png",
+                &vec![],
+                &Metadata::new(),
+                "\"etag\"",
+            )
+            .await;
+        assert!(put_result.is_err());
+
+        let get_result = store.get_file("my-bucket-thumbs", "../../../tmp/evil.png").await;
+        assert!(get_result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_local_fs_store_rejects_absolute_path_in_key() {
+        let scratch = ScratchDir::new();
+        let store = LocalFsStore::new(scratch.0.clone());
+
+        let result = store.get_file("my-bucket-thumbs", "/etc/passwd").await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_local_fs_store_get_file_missing() {
+        let scratch = ScratchDir::new();
+        let store = LocalFsStore::new(scratch.0.clone());
+
+        let result = store.get_file("my-bucket-thumbs", "does-not-exist.png").await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_local_fs_store_has_no_tags_or_etag() {
+        let scratch = ScratchDir::new();
+        let store = LocalFsStore::new(scratch.0.clone());
+
+        assert_eq!(store.get_tags("bucket", "key").await.unwrap(), Vec::<(String, String)>::new());
+        assert_eq!(store.get_metadata("bucket", "key").await.unwrap(), Metadata::new());
+        assert!(store.get_etag("bucket", "key").await.is_err());
+        assert_eq!(store.get_existing_source_etag("bucket", "key").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_local_fs_store_presigned_url_is_a_file_uri() {
+        let scratch = ScratchDir::new();
+        let store = LocalFsStore::new(scratch.0.clone());
+
+        let url = store
+            .get_presigned_url("bucket", "photo-256.png", Duration::from_secs(60))
+            .await
+            .unwrap();
+
+        assert!(url.starts_with("file://"));
+        assert!(url.ends_with("photo-256.png"));
+    }
+
+    #[test]
+    fn test_destination_parse_defaults_to_s3_with_thumbs_suffix() {
+        let destination = Destination::parse("", "").unwrap();
+        assert_eq!(destination.bucket_for("photos"), "photos-thumbs");
+
+        let destination = Destination::parse("s3://", "").unwrap();
+        assert_eq!(destination.bucket_for("photos"), "photos-thumbs");
+    }
+
+    #[test]
+    fn test_destination_parse_s3_with_custom_suffix() {
+        let destination = Destination::parse("s3://", "-resized").unwrap();
+        assert_eq!(destination.bucket_for("photos"), "photos-resized");
+    }
+
+    #[test]
+    fn test_destination_parse_file_uri() {
+        let destination = Destination::parse("file:///var/thumbnails", "").unwrap();
+        match destination {
+            Destination::LocalDir(path) => assert_eq!(path, PathBuf::from("/var/thumbnails")),
+            Destination::S3BucketSuffix(_) => panic!("expected a LocalDir destination"),
+        }
+    }
+
+    #[test]
+    fn test_destination_parse_rejects_unsupported_scheme() {
+        assert!(Destination::parse("ftp://example.com", "").is_err());
+    }
+
+    #[test]
+    fn test_source_parse_defaults_to_s3() {
+        assert!(matches!(Source::parse("").unwrap(), Source::S3));
+        assert!(matches!(Source::parse("s3://").unwrap(), Source::S3));
+    }
+
+    #[test]
+    fn test_source_parse_file_uri() {
+        match Source::parse("file:///var/uploads").unwrap() {
+            Source::LocalDir(path) => assert_eq!(path, PathBuf::from("/var/uploads")),
+            Source::S3 => panic!("expected a LocalDir source"),
+        }
+    }
+
+    #[test]
+    fn test_source_parse_rejects_unsupported_scheme() {
+        assert!(Source::parse("ftp://example.com").is_err());
+    }
+}
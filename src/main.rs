@@ -1,9 +1,13 @@
+use std::time::Duration;
+
 use aws_lambda_events::{event::s3::S3Event, s3::S3EventRecord};
 use aws_sdk_s3::Client as S3Client;
 use aws_config::BehaviorVersion;
 use lambda_runtime::{run, service_fn, tracing, Error, LambdaEvent};
-use s3::{GetFile, PutFile};
+use backend::{Destination, LocalFsStore, Source};
+use s3::{GetFile, Metadata, PutFile, Tags};
 
+mod backend;
 mod s3;
 
 /**
@@ -14,15 +18,22 @@ This lambda handler
     * uploads the thumbnail to bucket "[original bucket name]-thumbs".
 
 Make sure that
-    * the created png file has no strange characters in the name
+    * the created file has no strange characters in the name
     * there is another bucket with "-thumbs" suffix in the name
-    * this lambda only gets event from png file creation
     * this lambda has permission to put file into the "-thumbs" bucket
+
+Supported source formats are detected from the file's magic number (PNG, JPEG,
+GIF, BMP, WebP, TIFF); anything else is skipped with a log line.
 */
-pub(crate) async fn function_handler<T: PutFile + GetFile>(
+pub(crate) async fn function_handler<G: GetFile, P: PutFile>(
     event: LambdaEvent<S3Event>,
-    size: u32,
-    client: &T,
+    sizes: &[u32],
+    format: OutputFormat,
+    quality: u8,
+    destination: &Destination,
+    presign_expiry: Duration,
+    source: &G,
+    target: &P,
 ) -> Result<(), Error> {
     let records = event.payload.records;
 
@@ -35,7 +46,29 @@ pub(crate) async fn function_handler<T: PutFile + GetFile>(
             }
         };
 
-        let image = match client.get_file(&bucket, &key).await {
+        let source_etag = source.get_etag(&bucket, &key).await.unwrap_or_default();
+        let thumbs_bucket = destination.bucket_for(&bucket);
+
+        // Skip any size whose thumbnail is already up to date with the current source ETag,
+        // so duplicate S3 events and re-runs don't redo work that's already done.
+        let mut pending_sizes = Vec::new();
+
+        for &width in sizes {
+            let thumb_key = thumbnail_key(&key, width, format);
+            let existing_etag = target.get_existing_source_etag(&thumbs_bucket, &thumb_key).await;
+
+            if !source_etag.is_empty() && existing_etag.as_deref() == Some(source_etag.as_str()) {
+                tracing::info!("Thumbnail {} is up to date, skipping", thumb_key);
+            } else {
+                pending_sizes.push(width);
+            }
+        }
+
+        if pending_sizes.is_empty() {
+            continue;
+        }
+
+        let image = match source.get_file(&bucket, &key).await {
             Ok(vec) => vec,
             Err(msg) => {
                 tracing::info!("Can not get file from S3: {}", msg);
@@ -43,7 +76,16 @@ pub(crate) async fn function_handler<T: PutFile + GetFile>(
             }
         };
 
-        let thumbnail = match get_thumbnail(image, size) {
+        let source_mime = match sniff_mime(&image) {
+            Ok(mime) => mime,
+            Err(msg) => {
+                tracing::info!("Skipping unsupported object {}: {}", key, msg);
+                continue;
+            }
+        };
+
+        // Decodes the source once and produces one thumbnail per size that still needs it
+        let thumbnails = match get_thumbnail(image, source_mime, &pending_sizes, format, quality) {
             Ok(vec) => vec,
             Err(msg) => {
                 tracing::info!("Can not create thumbnail: {}", msg);
@@ -51,21 +93,245 @@ pub(crate) async fn function_handler<T: PutFile + GetFile>(
             }
         };
 
-        let mut thumbs_bucket = bucket.to_owned();
-        thumbs_bucket.push_str("-thumbs");
+        // Preserve provenance by carrying the source object's tags and metadata onto the thumbnail
+        let source_tags = source.get_tags(&bucket, &key).await.unwrap_or_else(|msg| {
+            tracing::info!("Can not read source tags: {}", msg);
+            vec![]
+        });
 
-        // It uploads the thumbnail into a bucket name suffixed with "-thumbs"
-        // So it needs file creation permission into that bucket
+        let source_metadata = source.get_metadata(&bucket, &key).await.unwrap_or_else(|msg| {
+            tracing::info!("Can not read source metadata: {}", msg);
+            Metadata::new()
+        });
 
-        match client.put_file(&thumbs_bucket, &key, thumbnail).await {
-            Ok(msg) => tracing::info!(msg),
-            Err(msg) => tracing::info!("Can not upload thumbnail: {}", msg),
+        // It uploads the thumbnails into the configured destination (an S3 bucket suffixed
+        // with "-thumbs" by default, or a local directory). It needs write permission there.
+
+        for (width, thumbnail) in thumbnails {
+            let thumb_key = thumbnail_key(&key, width, format);
+            let thumb_tags = thumbnail_tags(&source_tags, &key, width);
+
+            match target
+                .put_file(
+                    &thumbs_bucket,
+                    &thumb_key,
+                    thumbnail,
+                    format.content_type(),
+                    &thumb_tags,
+                    &source_metadata,
+                    &source_etag,
+                )
+                .await
+            {
+                Ok(msg) => {
+                    tracing::info!(msg);
+
+                    match target.get_presigned_url(&thumbs_bucket, &thumb_key, presign_expiry).await {
+                        Ok(url) => tracing::info!("Thumbnail {} is available at {}", thumb_key, url),
+                        Err(msg) => tracing::info!("Can not presign thumbnail {}: {}", thumb_key, msg),
+                    }
+                }
+                Err(msg) => tracing::info!("Can not upload thumbnail (size {}): {}", width, msg),
+            }
         }
     }
 
     Ok(())
 }
 
+/// Output codec used to encode generated thumbnails.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum OutputFormat {
+    Png,
+    WebP,
+    Jpeg,
+}
+
+impl OutputFormat {
+    /// File extension (without the leading dot) used for the uploaded key.
+    fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Png => "png",
+            OutputFormat::WebP => "webp",
+            OutputFormat::Jpeg => "jpg",
+        }
+    }
+
+    /// Content-type to set on the uploaded thumbnail object.
+    fn content_type(&self) -> &'static str {
+        match self {
+            OutputFormat::Png => "image/png",
+            OutputFormat::WebP => "image/webp",
+            OutputFormat::Jpeg => "image/jpeg",
+        }
+    }
+
+    /// Reads `THUMBNAIL_OUTPUT_FORMAT` from the environment, defaulting to PNG.
+    fn from_env() -> Self {
+        match std::env::var("THUMBNAIL_OUTPUT_FORMAT") {
+            Ok(format) if format.eq_ignore_ascii_case("webp") => OutputFormat::WebP,
+            Ok(format) if format.eq_ignore_ascii_case("jpeg") || format.eq_ignore_ascii_case("jpg") => OutputFormat::Jpeg,
+            _ => OutputFormat::Png,
+        }
+    }
+}
+
+/// Builds the thumbnail's destination key from the source key, a target width and the output format.
+///
+/// A width of `0` keeps the original key stem unchanged; any other width is inserted
+/// before the extension (`photo.png` -> `photo-256.webp`). The extension always matches
+/// the chosen output format.
+fn thumbnail_key(key: &str, width: u32, format: OutputFormat) -> String {
+    let (dir, filename) = match key.rfind('/') {
+        Some(idx) => (&key[..=idx], &key[idx + 1..]),
+        None => ("", key),
+    };
+
+    let stem = match filename.rfind('.') {
+        Some(idx) => format!("{}{}", dir, &filename[..idx]),
+        None => key.to_owned(),
+    };
+
+    match width {
+        0 => format!("{}.{}", stem, format.extension()),
+        width => format!("{}-{}.{}", stem, width, format.extension()),
+    }
+}
+
+/// Builds the tag set a generated thumbnail is uploaded with: the source object's own tags
+/// plus system tags recording the thumbnail's provenance. If the source object already carries
+/// a `thumbnail-of`/`thumbnail-size` tag of its own (e.g. it's itself a thumbnail), that tag is
+/// overwritten rather than duplicated, since S3 rejects a tag set with repeated keys.
+fn thumbnail_tags(source_tags: &Tags, source_key: &str, width: u32) -> Tags {
+    let mut tags: Tags = source_tags
+        .iter()
+        .filter(|(key, _)| key != "thumbnail-of" && key != "thumbnail-size")
+        .cloned()
+        .collect();
+    tags.push(("thumbnail-of".to_owned(), source_key.to_owned()));
+    tags.push(("thumbnail-size".to_owned(), width.to_string()));
+    tags
+}
+
+/// Sniffs the actual image format from the downloaded bytes' magic number, rather than
+/// assuming PNG, so JPEG/GIF/TIFF/WebP uploads are handled instead of failing or decoding garbage.
+fn sniff_mime(bytes: &[u8]) -> Result<mime::Mime, String> {
+    use image::ImageFormat;
+
+    match image::guess_format(bytes) {
+        Ok(ImageFormat::Png) => Ok(mime::IMAGE_PNG),
+        Ok(ImageFormat::Jpeg) => Ok(mime::IMAGE_JPEG),
+        Ok(ImageFormat::Gif) => Ok(mime::IMAGE_GIF),
+        Ok(ImageFormat::Bmp) => Ok(mime::IMAGE_BMP),
+        Ok(ImageFormat::WebP) => Ok("image/webp".parse().unwrap()),
+        Ok(ImageFormat::Tiff) => Ok("image/tiff".parse().unwrap()),
+        Ok(other) => Err(format!("Unsupported image format: {:?}", other)),
+        Err(err) => Err(format!("Could not recognize image format: {}", err)),
+    }
+}
+
+#[cfg(test)]
+mod key_mime_and_tag_tests {
+    use super::*;
+
+    #[test]
+    fn test_thumbnail_key_appends_size_before_extension() {
+        assert_eq!(thumbnail_key("photo.png", 256, OutputFormat::Png), "photo-256.png");
+    }
+
+    #[test]
+    fn test_thumbnail_key_width_zero_keeps_stem() {
+        assert_eq!(thumbnail_key("photo.png", 0, OutputFormat::WebP), "photo.webp");
+    }
+
+    #[test]
+    fn test_thumbnail_key_no_extension() {
+        assert_eq!(thumbnail_key("photo", 256, OutputFormat::Png), "photo-256.png");
+    }
+
+    #[test]
+    fn test_thumbnail_key_nested_directory() {
+        assert_eq!(thumbnail_key("uploads/2024/photo.png", 256, OutputFormat::Png), "uploads/2024/photo-256.png");
+    }
+
+    #[test]
+    fn test_thumbnail_key_dotted_directory_name_is_not_mistaken_for_an_extension() {
+        assert_eq!(thumbnail_key("v1.2/photo", 256, OutputFormat::Png), "v1.2/photo-256.png");
+        assert_eq!(thumbnail_key("v1.2/photo.jpg", 256, OutputFormat::Jpeg), "v1.2/photo-256.jpg");
+    }
+
+    #[test]
+    fn test_sniff_mime_detects_png() {
+        let png_magic = [0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A];
+        assert_eq!(sniff_mime(&png_magic).unwrap(), mime::IMAGE_PNG);
+    }
+
+    #[test]
+    fn test_sniff_mime_detects_jpeg() {
+        let jpeg_magic = [0xFF, 0xD8, 0xFF, 0xE0];
+        assert_eq!(sniff_mime(&jpeg_magic).unwrap(), mime::IMAGE_JPEG);
+    }
+
+    #[test]
+    fn test_sniff_mime_detects_gif() {
+        let gif_magic = b"GIF89a";
+        assert_eq!(sniff_mime(gif_magic).unwrap(), mime::IMAGE_GIF);
+    }
+
+    #[test]
+    fn test_sniff_mime_detects_bmp() {
+        let bmp_magic = b"BM\0\0\0\0";
+        assert_eq!(sniff_mime(bmp_magic).unwrap(), mime::IMAGE_BMP);
+    }
+
+    #[test]
+    fn test_sniff_mime_detects_webp() {
+        let webp_magic = b"RIFF\0\0\0\0WEBP";
+        assert_eq!(sniff_mime(webp_magic).unwrap(), "image/webp".parse::<mime::Mime>().unwrap());
+    }
+
+    #[test]
+    fn test_sniff_mime_detects_tiff() {
+        let tiff_magic = [0x49, 0x49, 0x2A, 0x00];
+        assert_eq!(sniff_mime(&tiff_magic).unwrap(), "image/tiff".parse::<mime::Mime>().unwrap());
+    }
+
+    #[test]
+    fn test_sniff_mime_rejects_unrecognized_bytes() {
+        assert!(sniff_mime(b"not an image").is_err());
+    }
+
+    #[test]
+    fn test_thumbnail_tags_appends_system_tags() {
+        let source_tags: Tags = vec![("project".to_owned(), "gallery".to_owned())];
+        let tags = thumbnail_tags(&source_tags, "photo.png", 256);
+        assert_eq!(
+            tags,
+            vec![
+                ("project".to_owned(), "gallery".to_owned()),
+                ("thumbnail-of".to_owned(), "photo.png".to_owned()),
+                ("thumbnail-size".to_owned(), "256".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_thumbnail_tags_overwrites_existing_system_tags_instead_of_duplicating_them() {
+        let source_tags: Tags = vec![
+            ("thumbnail-of".to_owned(), "original.png".to_owned()),
+            ("thumbnail-size".to_owned(), "64".to_owned()),
+        ];
+        let tags = thumbnail_tags(&source_tags, "photo.png", 256);
+        assert_eq!(
+            tags,
+            vec![
+                ("thumbnail-of".to_owned(), "photo.png".to_owned()),
+                ("thumbnail-size".to_owned(), "256".to_owned()),
+            ]
+        );
+    }
+}
+
 fn get_file_props(record: S3EventRecord) -> Result<(String, String), String> {
     record
         .event_name
@@ -85,26 +351,87 @@ fn get_file_props(record: S3EventRecord) -> Result<(String, String), String> {
 }
 
 #[cfg(not(test))]
-fn get_thumbnail(vec: Vec<u8>, size: u32) -> Result<Vec<u8>, String> {
+fn get_thumbnail(
+    vec: Vec<u8>,
+    mime: mime::Mime,
+    sizes: &[u32],
+    format: OutputFormat,
+    quality: u8,
+) -> Result<Vec<(u32, Vec<u8>)>, String> {
+    match format {
+        OutputFormat::Png => get_thumbnail_png(vec, mime, sizes),
+        OutputFormat::WebP | OutputFormat::Jpeg => get_thumbnail_image(vec, sizes, format, quality),
+    }
+}
+
+#[cfg(not(test))]
+fn get_thumbnail_png(vec: Vec<u8>, mime: mime::Mime, sizes: &[u32]) -> Result<Vec<(u32, Vec<u8>)>, String> {
     use std::io::Cursor;
 
     use thumbnailer::{create_thumbnails, ThumbnailSize};
 
     let reader = Cursor::new(vec);
-    let mime = mime::IMAGE_PNG;
-    let sizes = [ThumbnailSize::Custom((size, size))];
+    let thumb_sizes: Vec<ThumbnailSize> = sizes.iter().map(|&size| ThumbnailSize::Custom((size, size))).collect();
 
-    let thumbnail = match create_thumbnails(reader, mime, sizes) {
-        Ok(mut thumbnails) => thumbnails.pop().ok_or("No thumbnail created")?,
+    let thumbnails = match create_thumbnails(reader, mime, thumb_sizes) {
+        Ok(thumbnails) => thumbnails,
         Err(thumb_error) => return Err(thumb_error.to_string()),
     };
 
-    let mut buf = Cursor::new(Vec::new());
+    Ok(sizes
+        .iter()
+        .zip(thumbnails.into_iter())
+        .filter_map(|(&size, thumbnail)| {
+            let mut buf = Cursor::new(Vec::new());
+            thumbnail
+                .write_png(&mut buf)
+                .map(|_| (size, buf.into_inner()))
+                .map_err(|_| tracing::info!("Can not encode thumbnail (size {}): write_png failed", size))
+                .ok()
+        })
+        .collect())
+}
 
-    match thumbnail.write_png(&mut buf) {
-        Ok(_) => Ok(buf.into_inner()),
-        Err(_) => Err("Unknown error when Thumbnail::write_png".to_string()),
-    }
+/// Decodes the source once into a `DynamicImage` and resizes/encodes it per size
+/// for codecs that the `thumbnailer` crate doesn't cover (WebP, JPEG with quality).
+#[cfg(not(test))]
+fn get_thumbnail_image(
+    vec: Vec<u8>,
+    sizes: &[u32],
+    format: OutputFormat,
+    quality: u8,
+) -> Result<Vec<(u32, Vec<u8>)>, String> {
+    use std::io::Cursor;
+
+    use image::ImageOutputFormat;
+
+    let source = image::load_from_memory(&vec).map_err(|err| err.to_string())?;
+
+    Ok(sizes
+        .iter()
+        .filter_map(|&size| {
+            let resized = source.thumbnail(size, size);
+
+            let encoded: Result<Vec<u8>, String> = match format {
+                OutputFormat::WebP => webp::Encoder::from_image(&resized)
+                    .map(|encoder| encoder.encode(quality as f32).to_vec())
+                    .map_err(|err| err.to_string()),
+                OutputFormat::Jpeg => {
+                    let mut buf = Cursor::new(Vec::new());
+                    resized
+                        .write_to(&mut buf, ImageOutputFormat::Jpeg(quality))
+                        .map(|_| buf.into_inner())
+                        .map_err(|err| err.to_string())
+                }
+                OutputFormat::Png => unreachable!("PNG is encoded via get_thumbnail_png"),
+            };
+
+            encoded
+                .map(|bytes| (size, bytes))
+                .map_err(|err| tracing::info!("Can not encode thumbnail (size {}): {}", size, err))
+                .ok()
+        })
+        .collect())
 }
 
 #[tokio::main]
@@ -116,21 +443,245 @@ async fn main() -> Result<(), Error> {
     let client = S3Client::new(&shared_config);
     let client_ref = &client;
 
-    let func = service_fn(move |event| async move { 
-        function_handler(event, 128, client_ref).await 
-    });
+    let sizes = [128, 256, 512];
+    let format = OutputFormat::from_env();
+    let quality: u8 = std::env::var("THUMBNAIL_QUALITY")
+        .ok()
+        .and_then(|q| q.parse().ok())
+        .unwrap_or(80);
+
+    let source_uri = std::env::var("THUMBNAIL_SOURCE_URI").unwrap_or_default();
+    let source = Source::parse(&source_uri)?;
 
-    run(func).await?;
+    let destination_uri = std::env::var("THUMBNAIL_DESTINATION_URI").unwrap_or_default();
+    let bucket_suffix = std::env::var("THUMBNAIL_BUCKET_SUFFIX").unwrap_or_default();
+    let destination = Destination::parse(&destination_uri, &bucket_suffix)?;
+
+    let presign_expiry_secs: u64 = std::env::var("THUMBNAIL_PRESIGN_EXPIRY_SECS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(3600);
+    let presign_expiry = Duration::from_secs(presign_expiry_secs);
+
+    match (source, destination.clone()) {
+        (Source::S3, Destination::S3BucketSuffix(_)) => {
+            let func = service_fn(move |event| async move {
+                function_handler(event, &sizes, format, quality, &destination, presign_expiry, client_ref, client_ref).await
+            });
+
+            run(func).await?;
+        }
+        (Source::S3, Destination::LocalDir(path)) => {
+            let local_target = LocalFsStore::new(path);
+
+            let func = service_fn(move |event| async move {
+                function_handler(event, &sizes, format, quality, &destination, presign_expiry, client_ref, &local_target).await
+            });
+
+            run(func).await?;
+        }
+        (Source::LocalDir(path), Destination::S3BucketSuffix(_)) => {
+            let local_source = LocalFsStore::new(path);
+
+            let func = service_fn(move |event| async move {
+                function_handler(event, &sizes, format, quality, &destination, presign_expiry, &local_source, client_ref).await
+            });
+
+            run(func).await?;
+        }
+        (Source::LocalDir(source_path), Destination::LocalDir(dest_path)) => {
+            let local_source = LocalFsStore::new(source_path);
+            let local_target = LocalFsStore::new(dest_path);
+
+            let func = service_fn(move |event| async move {
+                function_handler(event, &sizes, format, quality, &destination, presign_expiry, &local_source, &local_target).await
+            });
+
+            run(func).await?;
+        }
+    }
 
     Ok(())
 }
 
+/// The PNG magic number, long enough for `sniff_mime` to recognize as `image/png` without
+/// needing a fully valid image body — used by tests as stand-in "downloaded" bytes.
+#[cfg(test)]
+const TEST_PNG_BYTES: &[u8] = &[0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A];
+
 #[cfg(test)]
-fn get_thumbnail(vec: Vec<u8>, _size: u32) -> Result<Vec<u8>, String> {
-    let s = unsafe { std::str::from_utf8_unchecked(&vec) };
+fn get_thumbnail(
+    vec: Vec<u8>,
+    _mime: mime::Mime,
+    sizes: &[u32],
+    _format: OutputFormat,
+    _quality: u8,
+) -> Result<Vec<(u32, Vec<u8>)>, String> {
+    if vec == TEST_PNG_BYTES {
+        Ok(sizes.iter().map(|&size| (size, "THUMBNAIL".into())).collect())
+    } else {
+        Err("Input is not IMAGE".to_string())
+    }
+}
+
+#[cfg(test)]
+mod handler_tests {
+    use std::sync::Mutex;
+
+    use aws_lambda_events::s3::{S3Bucket, S3Entity, S3Object};
+    use lambda_runtime::Context;
+
+    use super::*;
+
+    struct MockSource {
+        etag: String,
+    }
+
+    #[async_trait::async_trait]
+    impl GetFile for MockSource {
+        async fn get_file(&self, _bucket: &str, _key: &str) -> Result<Vec<u8>, String> {
+            Ok(TEST_PNG_BYTES.to_vec())
+        }
+
+        async fn get_tags(&self, _bucket: &str, _key: &str) -> Result<Tags, String> {
+            Ok(vec![])
+        }
+
+        async fn get_metadata(&self, _bucket: &str, _key: &str) -> Result<Metadata, String> {
+            Ok(Metadata::new())
+        }
+
+        async fn get_etag(&self, _bucket: &str, _key: &str) -> Result<String, String> {
+            Ok(self.etag.clone())
+        }
+    }
+
+    #[derive(Default)]
+    struct MockTarget {
+        existing_etag: Option<String>,
+        uploaded_keys: Mutex<Vec<String>>,
+    }
+
+    #[async_trait::async_trait]
+    impl PutFile for MockTarget {
+        async fn put_file(
+            &self,
+            _bucket: &str,
+            key: &str,
+            _bytes: Vec<u8>,
+            _content_type: &str,
+            _tags: &Tags,
+            _metadata: &Metadata,
+            _source_etag: &str,
+        ) -> Result<String, String> {
+            self.uploaded_keys.lock().unwrap().push(key.to_owned());
+            Ok("Uploaded".to_string())
+        }
+
+        async fn get_existing_source_etag(&self, _bucket: &str, _key: &str) -> Option<String> {
+            self.existing_etag.clone()
+        }
+
+        async fn get_presigned_url(&self, bucket: &str, key: &str, _expires_in: Duration) -> Result<String, String> {
+            Ok(format!("https://{}.example.com/{}", bucket, key))
+        }
+    }
+
+    fn test_event(bucket: &str, key: &str) -> LambdaEvent<S3Event> {
+        let record = S3EventRecord {
+            event_name: Some("ObjectCreated:Put".to_string()),
+            s3: S3Entity {
+                bucket: S3Bucket {
+                    name: Some(bucket.to_owned()),
+                    ..Default::default()
+                },
+                object: S3Object {
+                    key: Some(key.to_owned()),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        LambdaEvent {
+            payload: S3Event { records: vec![record] },
+            context: Context::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_skips_upload_when_thumbnail_is_up_to_date() {
+        let source = MockSource { etag: "\"same-etag\"".to_string() };
+        let target = MockTarget {
+            existing_etag: Some("\"same-etag\"".to_string()),
+            ..Default::default()
+        };
+        let destination = Destination::S3BucketSuffix("-thumbs".to_string());
+
+        function_handler(
+            test_event("bucket", "photo.png"),
+            &[128],
+            OutputFormat::Png,
+            80,
+            &destination,
+            Duration::from_secs(60),
+            &source,
+            &target,
+        )
+        .await
+        .unwrap();
+
+        assert!(target.uploaded_keys.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_uploads_when_source_etag_differs_from_existing_thumbnail() {
+        let source = MockSource { etag: "\"new-etag\"".to_string() };
+        let target = MockTarget {
+            existing_etag: Some("\"old-etag\"".to_string()),
+            ..Default::default()
+        };
+        let destination = Destination::S3BucketSuffix("-thumbs".to_string());
+
+        function_handler(
+            test_event("bucket", "photo.png"),
+            &[128],
+            OutputFormat::Png,
+            80,
+            &destination,
+            Duration::from_secs(60),
+            &source,
+            &target,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(target.uploaded_keys.lock().unwrap().as_slice(), &["photo-128.png".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_uploads_when_no_existing_thumbnail() {
+        let source = MockSource { etag: "\"etag\"".to_string() };
+        let target = MockTarget::default();
+        let destination = Destination::S3BucketSuffix("-thumbs".to_string());
+
+        function_handler(
+            test_event("bucket", "photo.png"),
+            &[128, 256],
+            OutputFormat::Png,
+            80,
+            &destination,
+            Duration::from_secs(60),
+            &source,
+            &target,
+        )
+        .await
+        .unwrap();
 
-    match s {
-        "IMAGE" => Ok("THUMBNAIL".into()),
-        _ => Err("Input is not IMAGE".to_string()),
+        let uploaded = target.uploaded_keys.lock().unwrap();
+        assert_eq!(uploaded.len(), 2);
+        assert!(uploaded.contains(&"photo-128.png".to_string()));
+        assert!(uploaded.contains(&"photo-256.png".to_string()));
     }
 }
@@ -1,52 +1,314 @@
+use std::time::Duration;
+
 use async_trait::async_trait;
-use aws_sdk_s3::operation::get_object::GetObjectError;
+use aws_sdk_s3::presigning::PresigningConfig;
+use aws_sdk_s3::types::CompletedMultipartUpload;
+use aws_sdk_s3::types::CompletedPart;
 use aws_sdk_s3::Client as S3Client;
 use aws_sdk_s3::primitives::ByteStream;
 use lambda_runtime::tracing;
 
+/// Objects at or above this size are uploaded via multipart upload instead of a single `put_object`.
+const MULTIPART_THRESHOLD: usize = 5 * 1024 * 1024;
+
+/// Chunk size used for each part of a multipart upload. Must be at least 5 MiB per the S3 API,
+/// except for the final part.
+const MULTIPART_CHUNK_SIZE: usize = 5 * 1024 * 1024;
+
+/// An object tag set, as `(key, value)` pairs.
+pub type Tags = Vec<(String, String)>;
+
+/// An object's user-defined metadata (the `x-amz-meta-*` headers), keyed without that prefix.
+pub type Metadata = std::collections::HashMap<String, String>;
+
+/// Metadata key a thumbnail's source ETag is stored under, so a later invocation can tell
+/// whether an existing thumbnail is still up to date with its source object.
+pub const SOURCE_ETAG_METADATA_KEY: &str = "thumbnail-source-etag";
+
 #[async_trait]
 pub trait GetFile {
-    async fn get_file(&self, bucket: &str, key: &str) -> Result<Vec<u8>, GetObjectError>;
+    async fn get_file(&self, bucket: &str, key: &str) -> Result<Vec<u8>, String>;
+
+    /// Fetches the object's tag set, so it can be carried over onto a derived object
+    /// (e.g. a generated thumbnail). Backends with no tagging concept may return an empty set.
+    async fn get_tags(&self, bucket: &str, key: &str) -> Result<Tags, String>;
+
+    /// Fetches the object's user-defined metadata, so it can be carried over onto a derived
+    /// object alongside its tags. Backends with no metadata concept may return an empty map.
+    async fn get_metadata(&self, bucket: &str, key: &str) -> Result<Metadata, String>;
+
+    /// Fetches the object's ETag, used to decide whether an existing thumbnail is stale.
+    async fn get_etag(&self, bucket: &str, key: &str) -> Result<String, String>;
 }
 
 #[async_trait]
 pub trait PutFile {
-    async fn put_file(&self, bucket: &str, key: &str, bytes: Vec<u8>) -> Result<String, String>;
+    async fn put_file(
+        &self,
+        bucket: &str,
+        key: &str,
+        bytes: Vec<u8>,
+        content_type: &str,
+        tags: &Tags,
+        metadata: &Metadata,
+        source_etag: &str,
+    ) -> Result<String, String>;
+
+    /// Returns the `SOURCE_ETAG_METADATA_KEY` metadata stored on an existing object, or `None`
+    /// if the object doesn't exist (or its ETag can't be determined).
+    async fn get_existing_source_etag(&self, bucket: &str, key: &str) -> Option<String>;
+
+    /// Generates a time-limited, publicly fetchable GET URL for an uploaded object, so a
+    /// caller can hand it to a client without making the bucket itself public.
+    async fn get_presigned_url(&self, bucket: &str, key: &str, expires_in: Duration) -> Result<String, String>;
 }
 
 #[async_trait]
 impl GetFile for S3Client {
-    async fn get_file(&self, bucket: &str, key: &str) -> Result<Vec<u8>, GetObjectError> {
+    // Streams the body in chunks instead of buffering it in one `collect()` call, which avoids
+    // briefly duplicating the object in memory on the way out of the SDK's internal buffers.
+    // The whole object still ends up in `bytes`, though: `get_thumbnail` decodes the image from
+    // a single in-memory buffer, so peak memory is still O(object size), not bounded.
+    async fn get_file(&self, bucket: &str, key: &str) -> Result<Vec<u8>, String> {
         tracing::info!("get file bucket {}, key {}", bucket, key);
 
         let output = self.get_object().bucket(bucket).key(key).send().await;
 
-        return match output {
-            Ok(response) => {
-                let bytes = response.body.collect().await.unwrap().to_vec();
-                tracing::info!("Object is downloaded, size is {}", bytes.len());
-                Ok(bytes)
-            }
+        let mut body = match output {
+            Ok(response) => response.body,
             Err(err) => {
                 let service_err = err.into_service_error();
                 let meta = service_err.meta();
                 tracing::info!("Error from aws when downloding: {}", meta.to_string());
-                Err(service_err)
+                return Err(meta.to_string());
             }
         };
+
+        let mut bytes = Vec::new();
+
+        while let Some(chunk) = body.try_next().await.map_err(|err| err.to_string())? {
+            bytes.extend_from_slice(&chunk);
+        }
+
+        tracing::info!("Object is downloaded, size is {}", bytes.len());
+        Ok(bytes)
+    }
+
+    async fn get_tags(&self, bucket: &str, key: &str) -> Result<Tags, String> {
+        let output = self
+            .get_object_tagging()
+            .bucket(bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(|err| err.into_service_error().meta().message().unwrap_or_default().to_string())?;
+
+        Ok(output
+            .tag_set()
+            .iter()
+            .map(|tag| (tag.key().to_owned(), tag.value().to_owned()))
+            .collect())
+    }
+
+    async fn get_metadata(&self, bucket: &str, key: &str) -> Result<Metadata, String> {
+        let head = self
+            .head_object()
+            .bucket(bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(|err| err.into_service_error().meta().message().unwrap_or_default().to_string())?;
+
+        Ok(head.metadata().cloned().unwrap_or_default())
+    }
+
+    async fn get_etag(&self, bucket: &str, key: &str) -> Result<String, String> {
+        let head = self
+            .head_object()
+            .bucket(bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(|err| err.into_service_error().meta().message().unwrap_or_default().to_string())?;
+
+        head.e_tag().map(str::to_owned).ok_or_else(|| "Object has no ETag".to_string())
     }
 }
 
 #[async_trait]
 impl PutFile for S3Client {
-    async fn put_file(&self, bucket: &str, key: &str, vec: Vec<u8>) -> Result<String, String> {
+    async fn put_file(
+        &self,
+        bucket: &str,
+        key: &str,
+        vec: Vec<u8>,
+        content_type: &str,
+        tags: &Tags,
+        metadata: &Metadata,
+        source_etag: &str,
+    ) -> Result<String, String> {
         tracing::info!("put file bucket {}, key {}", bucket, key);
+
+        if vec.len() >= MULTIPART_THRESHOLD {
+            return self.put_file_multipart(bucket, key, vec, content_type, tags, metadata, source_etag).await;
+        }
+
         let bytes = ByteStream::new(vec.into());
-        let result = self.put_object().bucket(bucket).key(key).body(bytes).send().await;
+        let mut request = self
+            .put_object()
+            .bucket(bucket)
+            .key(key)
+            .body(bytes)
+            .content_type(content_type)
+            .tagging(encode_tagging(tags));
+
+        for (meta_key, meta_value) in metadata {
+            request = request.metadata(meta_key, meta_value);
+        }
+
+        let result = request.metadata(SOURCE_ETAG_METADATA_KEY, source_etag).send().await;
 
         match result {
             Ok(_) => Ok(format!("Uploaded a file with key {} into {}", key, bucket)),
-            Err(err) => Err(err.into_service_error().meta().message().unwrap().to_string()),
+            Err(err) => Err(err.into_service_error().meta().message().unwrap_or_default().to_string()),
+        }
+    }
+
+    async fn get_existing_source_etag(&self, bucket: &str, key: &str) -> Option<String> {
+        let head = self.head_object().bucket(bucket).key(key).send().await.ok()?;
+        head.metadata()?.get(SOURCE_ETAG_METADATA_KEY).cloned()
+    }
+
+    async fn get_presigned_url(&self, bucket: &str, key: &str, expires_in: Duration) -> Result<String, String> {
+        let presigning_config = PresigningConfig::expires_in(expires_in).map_err(|err| err.to_string())?;
+
+        let presigned = self
+            .get_object()
+            .bucket(bucket)
+            .key(key)
+            .presigned(presigning_config)
+            .await
+            .map_err(|err| err.into_service_error().meta().message().unwrap_or_default().to_string())?;
+
+        Ok(presigned.uri().to_string())
+    }
+}
+
+/// URL-encodes a tag set into the `key1=value1&key2=value2` form the S3 API expects
+/// for the `x-amz-tagging` header (as used by `put_object`/`create_multipart_upload`).
+fn encode_tagging(tags: &Tags) -> String {
+    tags.iter()
+        .map(|(key, value)| format!("{}={}", percent_encode(key), percent_encode(value)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (byte as char).to_string(),
+            other => format!("%{:02X}", other),
+        })
+        .collect()
+}
+
+impl S3Client {
+    /// Uploads large thumbnails in chunks via the S3 multipart upload API instead of a
+    /// single `put_object`, so the SDK never has to hold the whole body in one request.
+    async fn put_file_multipart(
+        &self,
+        bucket: &str,
+        key: &str,
+        vec: Vec<u8>,
+        content_type: &str,
+        tags: &Tags,
+        metadata: &Metadata,
+        source_etag: &str,
+    ) -> Result<String, String> {
+        tracing::info!("Uploading {} bytes via multipart upload to {}/{}", vec.len(), bucket, key);
+
+        let mut create_request = self
+            .create_multipart_upload()
+            .bucket(bucket)
+            .key(key)
+            .content_type(content_type)
+            .tagging(encode_tagging(tags));
+
+        for (meta_key, meta_value) in metadata {
+            create_request = create_request.metadata(meta_key, meta_value);
+        }
+
+        let create = create_request
+            .metadata(SOURCE_ETAG_METADATA_KEY, source_etag)
+            .send()
+            .await
+            .map_err(|err| err.into_service_error().meta().message().unwrap_or_default().to_string())?;
+
+        let upload_id = create.upload_id().ok_or("Multipart upload has no upload id")?;
+
+        let mut completed_parts = Vec::new();
+
+        for (index, chunk) in vec.chunks(MULTIPART_CHUNK_SIZE).enumerate() {
+            let part_number = index as i32 + 1;
+
+            let part = match self
+                .upload_part()
+                .bucket(bucket)
+                .key(key)
+                .upload_id(upload_id)
+                .part_number(part_number)
+                .body(ByteStream::from(chunk.to_vec()))
+                .send()
+                .await
+            {
+                Ok(part) => part,
+                Err(err) => {
+                    let msg = err.into_service_error().meta().message().unwrap_or_default().to_string();
+                    self.abort_multipart_upload_best_effort(bucket, key, upload_id).await;
+                    return Err(msg);
+                }
+            };
+
+            let e_tag = part.e_tag().unwrap_or_default().to_string();
+            completed_parts.push(CompletedPart::builder().e_tag(e_tag).part_number(part_number).build());
+        }
+
+        let completed_upload = CompletedMultipartUpload::builder().set_parts(Some(completed_parts)).build();
+
+        if let Err(err) = self
+            .complete_multipart_upload()
+            .bucket(bucket)
+            .key(key)
+            .upload_id(upload_id)
+            .multipart_upload(completed_upload)
+            .send()
+            .await
+        {
+            let msg = err.into_service_error().meta().message().unwrap_or_default().to_string();
+            self.abort_multipart_upload_best_effort(bucket, key, upload_id).await;
+            return Err(msg);
+        }
+
+        Ok(format!("Uploaded a file with key {} into {} via multipart upload", key, bucket))
+    }
+
+    /// Aborts an in-progress multipart upload after a part/completion failure, so its
+    /// already-uploaded parts don't linger in the bucket until a lifecycle rule reaps them.
+    /// The abort itself is best-effort: its failure is logged, not propagated, since the
+    /// original upload error is what the caller needs to see.
+    async fn abort_multipart_upload_best_effort(&self, bucket: &str, key: &str, upload_id: &str) {
+        let result = self
+            .abort_multipart_upload()
+            .bucket(bucket)
+            .key(key)
+            .upload_id(upload_id)
+            .send()
+            .await;
+
+        if let Err(err) = result {
+            let msg = err.into_service_error().meta().message().unwrap_or_default().to_string();
+            tracing::info!("Can not abort multipart upload {} for {}/{}: {}", upload_id, bucket, key, msg);
         }
     }
 }
@@ -54,23 +316,50 @@ impl PutFile for S3Client {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use aws_sdk_s3::operation::get_object::GetObjectError;
-    // use aws_sdk_s3::error::SdkError;
     use async_trait::async_trait;
 
     struct MockS3Client;
     #[async_trait]
     impl GetFile for MockS3Client {
-        async fn get_file(&self, _bucket: &str, _key: &str) -> Result<Vec<u8>, GetObjectError> {
+        async fn get_file(&self, _bucket: &str, _key: &str) -> Result<Vec<u8>, String> {
             Ok(vec![1, 2, 3, 4, 5])
         }
+
+        async fn get_tags(&self, _bucket: &str, _key: &str) -> Result<Tags, String> {
+            Ok(vec![])
+        }
+
+        async fn get_metadata(&self, _bucket: &str, _key: &str) -> Result<Metadata, String> {
+            Ok(Metadata::new())
+        }
+
+        async fn get_etag(&self, _bucket: &str, _key: &str) -> Result<String, String> {
+            Ok("\"mock-etag\"".to_string())
+        }
     }
 
     #[async_trait]
     impl PutFile for MockS3Client {
-        async fn put_file(&self, _bucket: &str, _key: &str, _bytes: Vec<u8>) -> Result<String, String> {
+        async fn put_file(
+            &self,
+            _bucket: &str,
+            _key: &str,
+            _bytes: Vec<u8>,
+            _content_type: &str,
+            _tags: &Tags,
+            _metadata: &Metadata,
+            _source_etag: &str,
+        ) -> Result<String, String> {
             Ok("Mock put success".to_string())
         }
+
+        async fn get_existing_source_etag(&self, _bucket: &str, _key: &str) -> Option<String> {
+            None
+        }
+
+        async fn get_presigned_url(&self, bucket: &str, key: &str, _expires_in: Duration) -> Result<String, String> {
+            Ok(format!("https://{}.example.com/{}", bucket, key))
+        }
     }
 
     #[tokio::test]
@@ -84,8 +373,16 @@ mod tests {
     #[tokio::test]
     async fn test_put_file() {
         let client = MockS3Client {};
-        let result = client.put_file("dummy_bucket", "dummy_key", vec![1, 2, 3]).await;
+        let result = client
+            .put_file("dummy_bucket", "dummy_key", vec![1, 2, 3], "image/png", &vec![], &Metadata::new(), "\"mock-etag\"")
+            .await;
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), "Mock put success");
     }
+
+    #[test]
+    fn test_encode_tagging() {
+        let tags = vec![("thumbnail-of".to_string(), "a b/c".to_string())];
+        assert_eq!(encode_tagging(&tags), "thumbnail-of=a%20b%2Fc");
+    }
 }
\ No newline at end of file